@@ -8,12 +8,19 @@
 
 use base64::{engine::general_purpose::STANDARD, Engine};
 use jsonwebtoken::{decode_header, Algorithm};
+use rand::RngCore;
 use reqwest::blocking::Client;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
 const LOKI_URL: &str = "http://localhost:9000";
 const CLIENT_ID: &str = "test-client";
 const CLIENT_SECRET: &str = "test-secret";
+const REDIRECT_URI: &str = "http://localhost:8080/callback";
+/// The only audience this client should ever accept a token for.
+const EXPECTED_AUDIENCE: &str = CLIENT_ID;
+/// Clock-skew tolerance applied symmetrically to `exp`/`nbf`/`iat`.
+const LEEWAY_SECS: u64 = 60;
 
 #[derive(Debug, Serialize)]
 struct SessionRequest {
@@ -35,12 +42,36 @@ struct TokenResponse {
     token_type: String,
     #[allow(dead_code)]
     expires_in: u64,
+    id_token: Option<String>,
+}
+
+/// `aud` may be a single string or an array of strings (RFC 7519 §4.1.3).
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Audience {
+    Single(String),
+    Many(Vec<String>),
+}
+
+impl Audience {
+    /// True only when this audience resolves to exactly `expected` - not
+    /// merely to an array that happens to include it among others.
+    fn is_exactly(&self, expected: &str) -> bool {
+        match self {
+            Audience::Single(aud) => aud == expected,
+            Audience::Many(auds) => auds.len() == 1 && auds[0] == expected,
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
 struct Claims {
     exp: Option<u64>,
+    nbf: Option<u64>,
+    iat: Option<u64>,
     iss: Option<String>,
+    aud: Option<Audience>,
+    nonce: Option<String>,
 }
 
 #[derive(Debug)]
@@ -50,6 +81,198 @@ struct TestResult {
     message: String,
 }
 
+/// Request parameters for `/authorize`. Mirrors the subset of RFC 6749 /
+/// PKCE (RFC 7636) parameters OIDC-Loki understands. Sent form-urlencoded,
+/// like the `/token` leg of the same flow.
+#[derive(Debug, Serialize)]
+struct AuthorizeRequest {
+    client_id: String,
+    redirect_uri: String,
+    response_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    code_challenge: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    code_challenge_method: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    nonce: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AuthorizeResponse {
+    code: String,
+}
+
+/// A single entry from the JWKS document (RFC 7517). Only `kid` matters for
+/// the allowlist check; the rest of the key material is handled by a real
+/// JOSE library.
+#[derive(Debug, Deserialize)]
+struct Jwk {
+    kid: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct JwksResponse {
+    keys: Vec<Jwk>,
+}
+
+/// Fetch the published JWKS and return the set of `kid`s it vouches for.
+fn fetch_jwks(client: &Client) -> Vec<String> {
+    match client
+        .get(format!("{}/.well-known/jwks.json", LOKI_URL))
+        .send()
+    {
+        Ok(response) if response.status().is_success() => response
+            .json::<JwksResponse>()
+            .map(|jwks| jwks.keys.into_iter().map(|k| k.kid).collect())
+            .unwrap_or_default(),
+        Ok(response) => {
+            println!("  SKIP: JWKS fetch failed: {}", response.status());
+            Vec::new()
+        }
+        Err(e) => {
+            println!("  SKIP: Could not fetch JWKS: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+/// OIDC discovery document, per the subset of
+/// https://openid.net/specs/openid-connect-discovery-1_0.html that
+/// OIDC-Loki publishes.
+#[derive(Debug, Deserialize)]
+struct DiscoveryDocument {
+    issuer: String,
+    #[allow(dead_code)]
+    authorization_endpoint: String,
+    #[allow(dead_code)]
+    token_endpoint: String,
+    #[allow(dead_code)]
+    end_session_endpoint: String,
+}
+
+/// Claims carried by an OIDC back-channel logout token (RFC Back-Channel
+/// Logout 1.0 §2.4): `events` must contain the logout event URI and at
+/// least one of `sub`/`sid` must identify the session being logged out.
+#[derive(Debug, Deserialize)]
+struct LogoutClaims {
+    iss: Option<String>,
+    sub: Option<String>,
+    sid: Option<String>,
+    jti: Option<String>,
+    events: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LogoutTokenResponse {
+    logout_token: String,
+}
+
+const BACKCHANNEL_LOGOUT_EVENT: &str = "http://schemas.openid.net/event/backchannel-logout";
+
+/// Fetch the OIDC discovery document.
+fn fetch_discovery(client: &Client, session_id: Option<&str>) -> Option<DiscoveryDocument> {
+    let mut request = client.get(format!("{}/.well-known/openid-configuration", LOKI_URL));
+    if let Some(sid) = session_id {
+        request = request.header("X-Loki-Session", sid);
+    }
+
+    match request.send() {
+        Ok(response) if response.status().is_success() => response.json().ok(),
+        Ok(response) => {
+            println!("  SKIP: Discovery fetch failed: {}", response.status());
+            None
+        }
+        Err(e) => {
+            println!("  SKIP: Could not fetch discovery document: {}", e);
+            None
+        }
+    }
+}
+
+/// Ask the server for a back-channel logout token bound to `session_id`.
+fn get_logout_token(client: &Client, session_id: &str) -> Option<String> {
+    match client
+        .post(format!("{}/backchannel-logout", LOKI_URL))
+        .header("X-Loki-Session", session_id)
+        .send()
+    {
+        Ok(response) if response.status().is_success() => response
+            .json::<LogoutTokenResponse>()
+            .ok()
+            .map(|r| r.logout_token),
+        Ok(response) => {
+            println!("  SKIP: Logout token request failed: {}", response.status());
+            None
+        }
+        Err(e) => {
+            println!("  SKIP: Could not get logout token: {}", e);
+            None
+        }
+    }
+}
+
+/// Validate a back-channel logout token: reject `alg:none`/symmetric
+/// signing, require the logout event and a session identifier, and require
+/// that its issuer match the token issuer a client already trusts.
+fn validate_logout_token(token: &str, expected_iss: &str) -> Result<LogoutClaims, String> {
+    let header = decode_header(token).map_err(|e| format!("Invalid token format: {}", e))?;
+
+    match header.alg {
+        Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512 => {
+            return Err(format!(
+                "SECURITY: symmetric algorithm {:?} not allowed for logout tokens",
+                header.alg
+            ));
+        }
+        _ => {}
+    }
+
+    let parts: Vec<&str> = token.split('.').collect();
+    if parts.len() < 2 {
+        return Err("Invalid logout token structure".to_string());
+    }
+    let claims_json = STANDARD
+        .decode(parts[1])
+        .or_else(|_| base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(parts[1]))
+        .map_err(|e| format!("Invalid claims encoding: {}", e))?;
+    let claims: LogoutClaims = serde_json::from_slice(&claims_json)
+        .map_err(|e| format!("Invalid logout claims JSON: {}", e))?;
+
+    let events = claims
+        .events
+        .as_ref()
+        .and_then(|e| e.get(BACKCHANNEL_LOGOUT_EVENT));
+    if events.is_none() {
+        return Err("SECURITY: logout token is missing the backchannel-logout event".to_string());
+    }
+
+    if claims.sub.is_none() && claims.sid.is_none() {
+        return Err("SECURITY: logout token is missing both sub and sid".to_string());
+    }
+
+    match &claims.iss {
+        Some(iss) if iss == expected_iss => Ok(claims),
+        Some(iss) => Err(format!("SECURITY: logout token issuer mismatch: {}", iss)),
+        None => Err("SECURITY: logout token is missing iss".to_string()),
+    }
+}
+
+/// True when `url` shares its scheme, host, and port with the trusted
+/// issuer origin (`LOKI_URL`). Used to reject `jku`/`x5u` headers that
+/// point a naive client at an attacker-controlled key server; a mismatched
+/// port is a different origin even when the host matches.
+fn is_trusted_issuer_origin(url: &str) -> bool {
+    let issuer = reqwest::Url::parse(LOKI_URL).expect("LOKI_URL is a valid URL");
+    match reqwest::Url::parse(url) {
+        Ok(parsed) => {
+            parsed.scheme() == issuer.scheme()
+                && parsed.host_str() == issuer.host_str()
+                && parsed.port_or_known_default() == issuer.port_or_known_default()
+        }
+        Err(_) => false,
+    }
+}
+
 fn create_session(client: &Client, name: &str, mischief: Vec<&str>) -> Option<String> {
     let request = SessionRequest {
         name: name.to_string(),
@@ -104,11 +327,133 @@ fn get_token(client: &Client, session_id: Option<&str>) -> Option<String> {
     }
 }
 
+/// Generate a PKCE code verifier and its S256 code challenge (RFC 7636).
+fn generate_pkce_pair() -> (String, String) {
+    let mut verifier_bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut verifier_bytes);
+    let verifier = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(verifier_bytes);
+
+    let digest = Sha256::digest(verifier.as_bytes());
+    let challenge = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(digest);
+
+    (verifier, challenge)
+}
+
+/// Generate a fresh ID-token nonce (a client must use a new, unpredictable
+/// value per authorization request).
+fn generate_nonce() -> String {
+    let mut nonce_bytes = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut nonce_bytes);
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(nonce_bytes)
+}
+
+/// Start the authorization code flow and return the issued `code`.
+fn authorize(
+    client: &Client,
+    session_id: Option<&str>,
+    redirect_uri: &str,
+    code_challenge: Option<&str>,
+    nonce: Option<&str>,
+) -> Option<String> {
+    let request_body = AuthorizeRequest {
+        client_id: CLIENT_ID.to_string(),
+        redirect_uri: redirect_uri.to_string(),
+        response_type: "code".to_string(),
+        code_challenge: code_challenge.map(String::from),
+        code_challenge_method: code_challenge.map(|_| "S256".to_string()),
+        nonce: nonce.map(String::from),
+    };
+
+    let mut request = client.post(format!("{}/authorize", LOKI_URL)).form(&request_body);
+
+    if let Some(sid) = session_id {
+        request = request.header("X-Loki-Session", sid);
+    }
+
+    match request.send() {
+        Ok(response) if response.status().is_success() => {
+            response.json::<AuthorizeResponse>().ok().map(|a| a.code)
+        }
+        Ok(response) => {
+            println!("  SKIP: Authorize request failed: {}", response.status());
+            None
+        }
+        Err(e) => {
+            println!("  SKIP: Could not reach /authorize: {}", e);
+            None
+        }
+    }
+}
+
+/// Exchange an authorization code for a token, optionally presenting a PKCE
+/// verifier. Returns `Err` when the token endpoint rejects the exchange,
+/// which is the behavior a spec-compliant authorization server must show
+/// for a replayed code, a missing/invalid verifier, or a mismatched
+/// `redirect_uri`.
+fn exchange_code(
+    client: &Client,
+    session_id: Option<&str>,
+    code: &str,
+    redirect_uri: &str,
+    code_verifier: Option<&str>,
+) -> Result<TokenResponse, String> {
+    let auth = STANDARD.encode(format!("{}:{}", CLIENT_ID, CLIENT_SECRET));
+
+    let mut params = vec![
+        ("grant_type", "authorization_code"),
+        ("code", code),
+        ("redirect_uri", redirect_uri),
+    ];
+    if let Some(verifier) = code_verifier {
+        params.push(("code_verifier", verifier));
+    }
+
+    let mut request = client
+        .post(format!("{}/token", LOKI_URL))
+        .header("Authorization", format!("Basic {}", auth))
+        .form(&params);
+
+    if let Some(sid) = session_id {
+        request = request.header("X-Loki-Session", sid);
+    }
+
+    let response = request
+        .send()
+        .map_err(|e| format!("could not reach /token: {}", e))?;
+
+    if response.status().is_success() {
+        response
+            .json::<TokenResponse>()
+            .map_err(|e| format!("invalid token response: {}", e))
+    } else {
+        Err(format!("token endpoint rejected exchange: {}", response.status()))
+    }
+}
+
+/// Decode a token's claims without verifying the signature (for demo
+/// purposes - a real client only reads claims after signature verification).
+fn decode_claims(token: &str) -> Result<Claims, String> {
+    let parts: Vec<&str> = token.split('.').collect();
+    if parts.len() < 2 {
+        return Err("Invalid token structure".to_string());
+    }
+
+    let claims_json = STANDARD
+        .decode(parts[1])
+        .or_else(|_| {
+            // Try URL-safe base64
+            base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(parts[1])
+        })
+        .map_err(|e| format!("Invalid claims encoding: {}", e))?;
+
+    serde_json::from_slice(&claims_json).map_err(|e| format!("Invalid claims JSON: {}", e))
+}
+
 /// Validate a token with security checks.
 ///
 /// In production, use a proper OIDC library.
 /// This example demonstrates the security checks your client should perform.
-fn validate_token(token: &str) -> Result<(), String> {
+fn validate_token(token: &str, allowed_kids: &[String]) -> Result<(), String> {
     // Parse the header
     let header = decode_header(token).map_err(|e| format!("Invalid token format: {}", e))?;
 
@@ -139,34 +484,34 @@ fn validate_token(token: &str) -> Result<(), String> {
         _ => {}
     }
 
-    // Decode claims without verification (for demo purposes)
-    let parts: Vec<&str> = token.split('.').collect();
-    if parts.len() < 2 {
-        return Err("Invalid token structure".to_string());
-    }
-
-    let claims_json = STANDARD
-        .decode(parts[1])
-        .or_else(|_| {
-            // Try URL-safe base64
-            base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(parts[1])
-        })
-        .map_err(|e| format!("Invalid claims encoding: {}", e))?;
+    let claims = decode_claims(token)?;
 
-    let claims: Claims =
-        serde_json::from_slice(&claims_json).map_err(|e| format!("Invalid claims JSON: {}", e))?;
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
 
-    // Security Check 3: Validate expiration
+    // Security Check 3: Validate expiration, with leeway for clock skew
     if let Some(exp) = claims.exp {
-        let now = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
-        if exp < now {
+        if exp + LEEWAY_SECS < now {
             return Err("SECURITY: token is expired".to_string());
         }
     }
 
+    // Security Check 3b: Reject a token that isn't valid yet (nbf)
+    if let Some(nbf) = claims.nbf {
+        if now + LEEWAY_SECS < nbf {
+            return Err("SECURITY: token is not yet valid (nbf is in the future)".to_string());
+        }
+    }
+
+    // Security Check 3c: Reject a token issued in the future (iat)
+    if let Some(iat) = claims.iat {
+        if iat > now + LEEWAY_SECS {
+            return Err("SECURITY: token was issued in the future (iat)".to_string());
+        }
+    }
+
     // Security Check 4: Validate issuer
     if let Some(iss) = &claims.iss {
         if iss != LOKI_URL {
@@ -174,10 +519,52 @@ fn validate_token(token: &str) -> Result<(), String> {
         }
     }
 
+    // Security Check 5: Validate audience (aud-confusion defense)
+    match &claims.aud {
+        Some(aud) if aud.is_exactly(EXPECTED_AUDIENCE) => {}
+        Some(aud) => {
+            return Err(format!(
+                "SECURITY: token audience does not match expected client: {:?}",
+                aud
+            ));
+        }
+        None => {
+            return Err("SECURITY: token is missing the aud claim".to_string());
+        }
+    }
+
+    // Security Check 6: Reject jku/x5u headers pointing outside the issuer
+    // origin (SSRF / key-substitution defense)
+    if let Some(jku) = &header.jku {
+        if !is_trusted_issuer_origin(jku) {
+            return Err(format!("SECURITY: jku points to an untrusted origin: {}", jku));
+        }
+    }
+    if let Some(x5u) = &header.x5u {
+        if !is_trusted_issuer_origin(x5u) {
+            return Err(format!("SECURITY: x5u points to an untrusted origin: {}", x5u));
+        }
+    }
+
+    // Security Check 7: Reject a kid that isn't in the pre-fetched JWKS
+    // allowlist (key-confusion defense). An empty allowlist means the JWKS
+    // could not be verified against, so a token naming a kid must be
+    // rejected rather than silently let through.
+    if let Some(kid) = &header.kid {
+        if allowed_kids.is_empty() {
+            return Err(
+                "SECURITY: no JWKS allowlist available to verify kid against".to_string(),
+            );
+        }
+        if !allowed_kids.contains(kid) {
+            return Err(format!("SECURITY: kid not in JWKS allowlist: {}", kid));
+        }
+    }
+
     Ok(())
 }
 
-fn test_alg_none(client: &Client) -> TestResult {
+fn test_alg_none(client: &Client, allowed_kids: &[String]) -> TestResult {
     println!("\nTest 1: Algorithm None Attack");
     println!("{}", "-".repeat(40));
 
@@ -203,7 +590,7 @@ fn test_alg_none(client: &Client) -> TestResult {
         }
     };
 
-    match validate_token(&token) {
+    match validate_token(&token, allowed_kids) {
         Ok(_) => {
             println!("  FAIL: Client accepted alg:none token!");
             TestResult {
@@ -232,7 +619,7 @@ fn test_alg_none(client: &Client) -> TestResult {
     }
 }
 
-fn test_key_confusion(client: &Client) -> TestResult {
+fn test_key_confusion(client: &Client, allowed_kids: &[String]) -> TestResult {
     println!("\nTest 2: Key Confusion Attack");
     println!("{}", "-".repeat(40));
 
@@ -258,7 +645,7 @@ fn test_key_confusion(client: &Client) -> TestResult {
         }
     };
 
-    match validate_token(&token) {
+    match validate_token(&token, allowed_kids) {
         Ok(_) => {
             println!("  FAIL: Client accepted key confusion token!");
             TestResult {
@@ -287,7 +674,7 @@ fn test_key_confusion(client: &Client) -> TestResult {
     }
 }
 
-fn test_temporal_tampering(client: &Client) -> TestResult {
+fn test_temporal_tampering(client: &Client, allowed_kids: &[String]) -> TestResult {
     println!("\nTest 3: Temporal Tampering (Expired Token)");
     println!("{}", "-".repeat(40));
 
@@ -313,7 +700,7 @@ fn test_temporal_tampering(client: &Client) -> TestResult {
         }
     };
 
-    match validate_token(&token) {
+    match validate_token(&token, allowed_kids) {
         Ok(_) => {
             println!("  FAIL: Client accepted expired token!");
             TestResult {
@@ -388,33 +775,845 @@ fn test_valid_token(client: &Client) -> TestResult {
     }
 }
 
-fn main() {
-    println!("{}", "=".repeat(50));
-    println!("OIDC-Loki Rust Client Security Tests");
-    println!("{}", "=".repeat(50));
-
-    let client = Client::new();
+fn test_code_replay(client: &Client) -> TestResult {
+    println!("\nTest 5: Authorization Code Replay");
+    println!("{}", "-".repeat(40));
 
-    let results = vec![
-        test_alg_none(&client),
-        test_key_confusion(&client),
-        test_temporal_tampering(&client),
-        test_valid_token(&client),
-    ];
+    let session_id = match create_session(client, "rust-code-replay-test", vec!["code-replay"]) {
+        Some(id) => id,
+        None => {
+            return TestResult {
+                name: "code-replay",
+                passed: false,
+                message: "Could not create session".to_string(),
+            }
+        }
+    };
 
-    // Summary
-    println!("\n{}", "=".repeat(50));
-    println!("Summary");
-    println!("{}", "=".repeat(50));
+    let code = match authorize(client, Some(&session_id), REDIRECT_URI, None, None) {
+        Some(c) => c,
+        None => {
+            return TestResult {
+                name: "code-replay",
+                passed: false,
+                message: "Could not get authorization code".to_string(),
+            }
+        }
+    };
 
-    let passed = results.iter().filter(|r| r.passed).count();
-    let total = results.len();
-    println!("Passed: {}/{}", passed, total);
+    if let Err(e) = exchange_code(client, Some(&session_id), &code, REDIRECT_URI, None) {
+        return TestResult {
+            name: "code-replay",
+            passed: false,
+            message: format!("First exchange failed: {}", e),
+        };
+    }
 
-    for result in &results {
-        let status = if result.passed { "PASS" } else { "FAIL" };
-        println!("  [{}] {}: {}", status, result.name, result.message);
+    // The session has code-replay mischief enabled, so a replayed code is
+    // expected to succeed here - that's the vulnerability this probe exists
+    // to demonstrate. If the server instead rejects it, the mischief never
+    // manifested and the probe didn't exercise anything.
+    match exchange_code(client, Some(&session_id), &code, REDIRECT_URI, None) {
+        Ok(_) => {
+            println!("  PASS: Server accepted the replayed authorization code (code-replay mischief confirmed)");
+            TestResult {
+                name: "code-replay",
+                passed: true,
+                message: "SECURITY: server accepted a replayed authorization code".to_string(),
+            }
+        }
+        Err(e) => {
+            println!("  FAIL: code-replay mischief did not manifest - replayed code was rejected");
+            TestResult {
+                name: "code-replay",
+                passed: false,
+                message: format!("Expected the replayed code to be accepted, but it was rejected: {}", e),
+            }
+        }
     }
+}
+
+fn test_pkce_downgrade(client: &Client) -> TestResult {
+    println!("\nTest 6: PKCE Downgrade Attack");
+    println!("{}", "-".repeat(40));
+
+    let session_id = match create_session(client, "rust-pkce-downgrade-test", vec!["pkce-downgrade"]) {
+        Some(id) => id,
+        None => {
+            return TestResult {
+                name: "pkce-downgrade",
+                passed: false,
+                message: "Could not create session".to_string(),
+            }
+        }
+    };
+
+    let (_verifier, challenge) = generate_pkce_pair();
+
+    let code = match authorize(client, Some(&session_id), REDIRECT_URI, Some(&challenge), None) {
+        Some(c) => c,
+        None => {
+            return TestResult {
+                name: "pkce-downgrade",
+                passed: false,
+                message: "Could not get authorization code".to_string(),
+            }
+        }
+    };
+
+    // Deliberately omit the code_verifier even though code_challenge was sent.
+    // The session has pkce-downgrade mischief enabled, so the exchange is
+    // expected to succeed anyway - that's the vulnerability this probe
+    // exists to demonstrate.
+    match exchange_code(client, Some(&session_id), &code, REDIRECT_URI, None) {
+        Ok(_) => {
+            println!("  PASS: Server issued a token without a PKCE verifier (pkce-downgrade mischief confirmed)");
+            TestResult {
+                name: "pkce-downgrade",
+                passed: true,
+                message: "SECURITY: server issued a token despite a missing code_verifier".to_string(),
+            }
+        }
+        Err(e) => {
+            println!("  FAIL: pkce-downgrade mischief did not manifest - exchange without a verifier was rejected");
+            TestResult {
+                name: "pkce-downgrade",
+                passed: false,
+                message: format!("Expected the exchange to succeed without a verifier, but it was rejected: {}", e),
+            }
+        }
+    }
+}
+
+fn test_redirect_uri_mismatch(client: &Client) -> TestResult {
+    println!("\nTest 7: Redirect URI Mismatch");
+    println!("{}", "-".repeat(40));
+
+    let session_id = match create_session(
+        client,
+        "rust-redirect-uri-mismatch-test",
+        vec!["redirect-uri-mismatch"],
+    ) {
+        Some(id) => id,
+        None => {
+            return TestResult {
+                name: "redirect-uri-mismatch",
+                passed: false,
+                message: "Could not create session".to_string(),
+            }
+        }
+    };
+
+    let code = match authorize(client, Some(&session_id), REDIRECT_URI, None, None) {
+        Some(c) => c,
+        None => {
+            return TestResult {
+                name: "redirect-uri-mismatch",
+                passed: false,
+                message: "Could not get authorization code".to_string(),
+            }
+        }
+    };
+
+    // The session has redirect-uri-mismatch mischief enabled, so the
+    // exchange is expected to succeed despite the mismatch - that's the
+    // vulnerability this probe exists to demonstrate.
+    let other_redirect_uri = "http://evil.example/callback";
+    match exchange_code(client, Some(&session_id), &code, other_redirect_uri, None) {
+        Ok(_) => {
+            println!("  PASS: Server issued a token despite a mismatched redirect_uri (redirect-uri-mismatch mischief confirmed)");
+            TestResult {
+                name: "redirect-uri-mismatch",
+                passed: true,
+                message: "SECURITY: server issued a token despite a redirect_uri mismatch".to_string(),
+            }
+        }
+        Err(e) => {
+            println!("  FAIL: redirect-uri-mismatch mischief did not manifest - mismatched redirect_uri was rejected");
+            TestResult {
+                name: "redirect-uri-mismatch",
+                passed: false,
+                message: format!("Expected the exchange to succeed despite the mismatch, but it was rejected: {}", e),
+            }
+        }
+    }
+}
+
+fn test_aud_confusion(client: &Client, allowed_kids: &[String]) -> TestResult {
+    println!("\nTest 8: Audience Confusion Attack");
+    println!("{}", "-".repeat(40));
+
+    let session_id = match create_session(client, "rust-aud-confusion-test", vec!["aud-confusion"]) {
+        Some(id) => id,
+        None => {
+            return TestResult {
+                name: "aud-confusion",
+                passed: false,
+                message: "Could not create session".to_string(),
+            }
+        }
+    };
+
+    let token = match get_token(client, Some(&session_id)) {
+        Some(t) => t,
+        None => {
+            return TestResult {
+                name: "aud-confusion",
+                passed: false,
+                message: "Could not get token".to_string(),
+            }
+        }
+    };
+
+    match validate_token(&token, allowed_kids) {
+        Ok(_) => {
+            println!("  FAIL: Client accepted a token with the wrong audience!");
+            TestResult {
+                name: "aud-confusion",
+                passed: false,
+                message: "Client accepted token not intended for this client".to_string(),
+            }
+        }
+        Err(e) if e.to_lowercase().contains("aud") => {
+            println!("  PASS: Client correctly rejected the mismatched audience");
+            println!("  Error: {}", e);
+            TestResult {
+                name: "aud-confusion",
+                passed: true,
+                message: e,
+            }
+        }
+        Err(e) => {
+            println!("  FAIL: Token was rejected, but not for the audience mismatch this test exercises");
+            TestResult {
+                name: "aud-confusion",
+                passed: false,
+                message: format!("Expected an aud-related rejection, got: {}", e),
+            }
+        }
+    }
+}
+
+fn test_jku_injection(client: &Client, allowed_kids: &[String]) -> TestResult {
+    println!("\nTest 9: JKU Header Injection (SSRF / Key Substitution)");
+    println!("{}", "-".repeat(40));
+
+    let session_id = match create_session(client, "rust-jku-injection-test", vec!["jku-injection"]) {
+        Some(id) => id,
+        None => {
+            return TestResult {
+                name: "jku-injection",
+                passed: false,
+                message: "Could not create session".to_string(),
+            }
+        }
+    };
+
+    let token = match get_token(client, Some(&session_id)) {
+        Some(t) => t,
+        None => {
+            return TestResult {
+                name: "jku-injection",
+                passed: false,
+                message: "Could not get token".to_string(),
+            }
+        }
+    };
+
+    match validate_token(&token, allowed_kids) {
+        Ok(_) => {
+            println!("  FAIL: Client accepted a token with an untrusted jku!");
+            TestResult {
+                name: "jku-injection",
+                passed: false,
+                message: "Client fetched keys from an attacker-controlled jku".to_string(),
+            }
+        }
+        Err(e) if e.to_lowercase().contains("jku") || e.to_lowercase().contains("x5u") => {
+            println!("  PASS: Client correctly rejected the untrusted jku");
+            println!("  Error: {}", e);
+            TestResult {
+                name: "jku-injection",
+                passed: true,
+                message: e,
+            }
+        }
+        Err(e) => {
+            println!("  FAIL: Token was rejected, but not for the untrusted jku/x5u this test exercises");
+            TestResult {
+                name: "jku-injection",
+                passed: false,
+                message: format!("Expected a jku/x5u-related rejection, got: {}", e),
+            }
+        }
+    }
+}
+
+fn test_temporal_nbf(client: &Client, allowed_kids: &[String]) -> TestResult {
+    println!("\nTest 10: Temporal Tampering (nbf in the Future)");
+    println!("{}", "-".repeat(40));
+
+    let session_id = match create_session(client, "rust-temporal-nbf-test", vec!["nbf-future"]) {
+        Some(id) => id,
+        None => {
+            return TestResult {
+                name: "temporal-nbf",
+                passed: false,
+                message: "Could not create session".to_string(),
+            }
+        }
+    };
+
+    let token = match get_token(client, Some(&session_id)) {
+        Some(t) => t,
+        None => {
+            return TestResult {
+                name: "temporal-nbf",
+                passed: false,
+                message: "Could not get token".to_string(),
+            }
+        }
+    };
+
+    match validate_token(&token, allowed_kids) {
+        Ok(_) => {
+            println!("  FAIL: Client accepted a not-yet-valid token!");
+            TestResult {
+                name: "temporal-nbf",
+                passed: false,
+                message: "Client accepted token with future nbf".to_string(),
+            }
+        }
+        Err(e) if e.to_lowercase().contains("nbf") => {
+            println!("  PASS: Client correctly rejected the not-yet-valid token");
+            println!("  Error: {}", e);
+            TestResult {
+                name: "temporal-nbf",
+                passed: true,
+                message: e,
+            }
+        }
+        Err(e) => {
+            println!("  FAIL: Token was rejected, but not for the future nbf this test exercises");
+            TestResult {
+                name: "temporal-nbf",
+                passed: false,
+                message: format!("Expected an nbf-related rejection, got: {}", e),
+            }
+        }
+    }
+}
+
+fn test_temporal_iat(client: &Client, allowed_kids: &[String]) -> TestResult {
+    println!("\nTest 11: Temporal Tampering (iat in the Future)");
+    println!("{}", "-".repeat(40));
+
+    let session_id = match create_session(client, "rust-temporal-iat-test", vec!["iat-future"]) {
+        Some(id) => id,
+        None => {
+            return TestResult {
+                name: "temporal-iat",
+                passed: false,
+                message: "Could not create session".to_string(),
+            }
+        }
+    };
+
+    let token = match get_token(client, Some(&session_id)) {
+        Some(t) => t,
+        None => {
+            return TestResult {
+                name: "temporal-iat",
+                passed: false,
+                message: "Could not get token".to_string(),
+            }
+        }
+    };
+
+    match validate_token(&token, allowed_kids) {
+        Ok(_) => {
+            println!("  FAIL: Client accepted a token issued in the future!");
+            TestResult {
+                name: "temporal-iat",
+                passed: false,
+                message: "Client accepted token with future iat".to_string(),
+            }
+        }
+        Err(e) if e.to_lowercase().contains("iat") => {
+            println!("  PASS: Client correctly rejected the future-issued token");
+            println!("  Error: {}", e);
+            TestResult {
+                name: "temporal-iat",
+                passed: true,
+                message: e,
+            }
+        }
+        Err(e) => {
+            println!("  FAIL: Token was rejected, but not for the future iat this test exercises");
+            TestResult {
+                name: "temporal-iat",
+                passed: false,
+                message: format!("Expected an iat-related rejection, got: {}", e),
+            }
+        }
+    }
+}
+
+fn test_discovery_issuer_mismatch(client: &Client) -> TestResult {
+    println!("\nTest 12: Discovery/Token Issuer Mismatch");
+    println!("{}", "-".repeat(40));
+
+    let session_id = match create_session(
+        client,
+        "rust-discovery-issuer-mismatch-test",
+        vec!["discovery-issuer-mismatch"],
+    ) {
+        Some(id) => id,
+        None => {
+            return TestResult {
+                name: "discovery-issuer-mismatch",
+                passed: false,
+                message: "Could not create session".to_string(),
+            }
+        }
+    };
+
+    let discovery = match fetch_discovery(client, Some(&session_id)) {
+        Some(d) => d,
+        None => {
+            return TestResult {
+                name: "discovery-issuer-mismatch",
+                passed: false,
+                message: "Could not fetch discovery document".to_string(),
+            }
+        }
+    };
+
+    let token = match get_token(client, Some(&session_id)) {
+        Some(t) => t,
+        None => {
+            return TestResult {
+                name: "discovery-issuer-mismatch",
+                passed: false,
+                message: "Could not get token".to_string(),
+            }
+        }
+    };
+
+    let claims = match decode_claims(&token) {
+        Ok(c) => c,
+        Err(e) => {
+            return TestResult {
+                name: "discovery-issuer-mismatch",
+                passed: false,
+                message: format!("Could not decode token claims: {}", e),
+            }
+        }
+    };
+
+    match claims.iss {
+        Some(iss) if iss == discovery.issuer => {
+            println!("  FAIL: discovery-issuer-mismatch mischief did not manifest - discovery issuer matched token issuer");
+            TestResult {
+                name: "discovery-issuer-mismatch",
+                passed: false,
+                message: "Expected a mismatched discovery issuer under discovery-issuer-mismatch mischief, but it matched the token issuer"
+                    .to_string(),
+            }
+        }
+        Some(iss) => {
+            println!("  PASS: Client correctly detected the issuer mismatch");
+            println!("  discovery.issuer={} token.iss={}", discovery.issuer, iss);
+            TestResult {
+                name: "discovery-issuer-mismatch",
+                passed: true,
+                message: format!(
+                    "SECURITY: discovery issuer {} disagrees with token issuer {}",
+                    discovery.issuer, iss
+                ),
+            }
+        }
+        None => TestResult {
+            name: "discovery-issuer-mismatch",
+            passed: false,
+            message: "Token has no iss claim to compare".to_string(),
+        },
+    }
+}
+
+fn test_logout_token_forgery(client: &Client) -> TestResult {
+    println!("\nTest 13: Back-Channel Logout Token Forgery");
+    println!("{}", "-".repeat(40));
+
+    let session_id = match create_session(
+        client,
+        "rust-logout-token-forgery-test",
+        vec!["logout-token-forgery"],
+    ) {
+        Some(id) => id,
+        None => {
+            return TestResult {
+                name: "logout-token-forgery",
+                passed: false,
+                message: "Could not create session".to_string(),
+            }
+        }
+    };
+
+    let logout_token = match get_logout_token(client, &session_id) {
+        Some(t) => t,
+        None => {
+            return TestResult {
+                name: "logout-token-forgery",
+                passed: false,
+                message: "Could not get logout token".to_string(),
+            }
+        }
+    };
+
+    match validate_logout_token(&logout_token, LOKI_URL) {
+        Ok(_) => {
+            println!("  FAIL: Client accepted a forged logout token!");
+            TestResult {
+                name: "logout-token-forgery",
+                passed: false,
+                message: "Client accepted an alg:none/malformed logout token".to_string(),
+            }
+        }
+        Err(e) => {
+            println!("  PASS: Client correctly rejected the forged logout token");
+            println!("  Error: {}", e);
+            TestResult {
+                name: "logout-token-forgery",
+                passed: true,
+                message: e,
+            }
+        }
+    }
+}
+
+fn test_logout_token_replay(client: &Client) -> TestResult {
+    println!("\nTest 14: Back-Channel Logout Token Replay");
+    println!("{}", "-".repeat(40));
+
+    let session_id = match create_session(
+        client,
+        "rust-logout-token-replay-test",
+        vec!["logout-token-replay"],
+    ) {
+        Some(id) => id,
+        None => {
+            return TestResult {
+                name: "logout-token-replay",
+                passed: false,
+                message: "Could not create session".to_string(),
+            }
+        }
+    };
+
+    let logout_token = match get_logout_token(client, &session_id) {
+        Some(t) => t,
+        None => {
+            return TestResult {
+                name: "logout-token-replay",
+                passed: false,
+                message: "Could not get logout token".to_string(),
+            }
+        }
+    };
+
+    let mut seen_jti = std::collections::HashSet::new();
+
+    // First delivery: a correct client validates the token and records its jti.
+    let first_claims = match validate_logout_token(&logout_token, LOKI_URL) {
+        Ok(c) => c,
+        Err(e) => {
+            return TestResult {
+                name: "logout-token-replay",
+                passed: false,
+                message: format!("Logout token failed basic validation: {}", e),
+            }
+        }
+    };
+    let jti = match first_claims.jti {
+        Some(jti) if !jti.is_empty() => jti,
+        _ => {
+            return TestResult {
+                name: "logout-token-replay",
+                passed: false,
+                message: "Logout token carried no jti, so replay cannot be tracked".to_string(),
+            }
+        }
+    };
+    seen_jti.insert(jti.clone());
+
+    // Second delivery of the exact same token: a correct client must
+    // recognize the jti it already consumed and refuse to act on it again.
+    let second_claims = match validate_logout_token(&logout_token, LOKI_URL) {
+        Ok(c) => c,
+        Err(e) => {
+            println!("  PASS: Logout token validation rejected the replayed logout token");
+            return TestResult {
+                name: "logout-token-replay",
+                passed: true,
+                message: e,
+            };
+        }
+    };
+    let replayed_jti = second_claims.jti.unwrap_or_default();
+
+    if seen_jti.contains(&replayed_jti) {
+        println!("  PASS: Client's jti tracking rejected the replayed logout token");
+        TestResult {
+            name: "logout-token-replay",
+            passed: true,
+            message: format!("Logout token jti {} was recognized as already processed", replayed_jti),
+        }
+    } else {
+        println!("  FAIL: Replayed logout token was accepted as a new event!");
+        TestResult {
+            name: "logout-token-replay",
+            passed: false,
+            message: "Client processed a replayed logout token twice".to_string(),
+        }
+    }
+}
+
+fn test_nonce_binding(client: &Client) -> TestResult {
+    println!("\nTest 15: ID Token Nonce Binding");
+    println!("{}", "-".repeat(40));
+
+    let session_id = match create_session(client, "rust-nonce-mismatch-test", vec!["nonce-mismatch"]) {
+        Some(id) => id,
+        None => {
+            return TestResult {
+                name: "nonce-binding",
+                passed: false,
+                message: "Could not create session".to_string(),
+            }
+        }
+    };
+
+    let expected_nonce = generate_nonce();
+
+    let code = match authorize(
+        client,
+        Some(&session_id),
+        REDIRECT_URI,
+        None,
+        Some(&expected_nonce),
+    ) {
+        Some(c) => c,
+        None => {
+            return TestResult {
+                name: "nonce-binding",
+                passed: false,
+                message: "Could not get authorization code".to_string(),
+            }
+        }
+    };
+
+    let token_response = match exchange_code(client, Some(&session_id), &code, REDIRECT_URI, None) {
+        Ok(t) => t,
+        Err(e) => {
+            return TestResult {
+                name: "nonce-binding",
+                passed: false,
+                message: format!("Could not exchange code: {}", e),
+            }
+        }
+    };
+
+    let id_token = match token_response.id_token {
+        Some(t) => t,
+        None => {
+            return TestResult {
+                name: "nonce-binding",
+                passed: false,
+                message: "Token response did not include an id_token".to_string(),
+            }
+        }
+    };
+
+    let claims = match decode_claims(&id_token) {
+        Ok(c) => c,
+        Err(e) => {
+            return TestResult {
+                name: "nonce-binding",
+                passed: false,
+                message: format!("Could not decode id_token claims: {}", e),
+            }
+        }
+    };
+
+    match &claims.nonce {
+        Some(nonce) if nonce == &expected_nonce => {
+            println!("  FAIL: nonce-mismatch mischief did not manifest - id_token nonce matched!");
+            TestResult {
+                name: "nonce-binding",
+                passed: false,
+                message: "Expected a mismatched nonce under nonce-mismatch mischief, but got a match"
+                    .to_string(),
+            }
+        }
+        Some(nonce) => {
+            println!("  PASS: Client correctly detected the nonce mismatch");
+            TestResult {
+                name: "nonce-binding",
+                passed: true,
+                message: format!(
+                    "SECURITY: id_token nonce mismatch: expected {}, got {}",
+                    expected_nonce, nonce
+                ),
+            }
+        }
+        None => {
+            println!("  PASS: Client correctly detected the missing nonce");
+            TestResult {
+                name: "nonce-binding",
+                passed: true,
+                message: "SECURITY: id_token is missing nonce".to_string(),
+            }
+        }
+    }
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Render `results` as a JUnit XML report (one `<testcase>` per mischief
+/// mode, with `<failure>` carrying the message when `passed` is false).
+fn format_junit(results: &[TestResult]) -> String {
+    let failures = results.iter().filter(|r| !r.passed).count();
+
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(&format!(
+        "<testsuite name=\"oidc-loki\" tests=\"{}\" failures=\"{}\">\n",
+        results.len(),
+        failures
+    ));
+    for result in results {
+        xml.push_str(&format!(
+            "  <testcase name=\"{}\" classname=\"oidc-loki\">\n",
+            xml_escape(result.name)
+        ));
+        if !result.passed {
+            xml.push_str(&format!(
+                "    <failure message=\"{}\">{}</failure>\n",
+                xml_escape(&result.message),
+                xml_escape(&result.message)
+            ));
+        }
+        xml.push_str("  </testcase>\n");
+    }
+    xml.push_str("</testsuite>\n");
+    xml
+}
+
+/// Render `results` as a SARIF 2.1.0 log: one `result` per failed mischief,
+/// with the mischief name as the rule ID.
+fn format_sarif(results: &[TestResult]) -> serde_json::Value {
+    let rules: Vec<serde_json::Value> = results
+        .iter()
+        .map(|r| serde_json::json!({ "id": r.name }))
+        .collect();
+
+    let sarif_results: Vec<serde_json::Value> = results
+        .iter()
+        .filter(|r| !r.passed)
+        .map(|r| {
+            serde_json::json!({
+                "ruleId": r.name,
+                "level": "error",
+                "message": { "text": r.message },
+            })
+        })
+        .collect();
+
+    serde_json::json!({
+        "$schema": "https://raw.githubusercontent.com/oasis-tcs/sarif-spec/master/Schemata/sarif-schema-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": { "driver": { "name": "oidc-loki", "rules": rules } },
+            "results": sarif_results,
+        }],
+    })
+}
+
+/// Emit a structured report when `OIDC_LOKI_OUTPUT_FORMAT` is set to
+/// `junit` or `sarif`, on top of the plain-text summary. Written to
+/// `OIDC_LOKI_OUTPUT_FILE` when set, otherwise to stdout.
+fn emit_structured_report(results: &[TestResult]) {
+    let format = match std::env::var("OIDC_LOKI_OUTPUT_FORMAT") {
+        Ok(f) => f,
+        Err(_) => return,
+    };
+
+    let report = match format.as_str() {
+        "junit" => format_junit(results),
+        "sarif" => serde_json::to_string_pretty(&format_sarif(results))
+            .expect("SARIF report serializes"),
+        other => {
+            eprintln!("Unknown OIDC_LOKI_OUTPUT_FORMAT: {} (expected junit or sarif)", other);
+            return;
+        }
+    };
+
+    match std::env::var("OIDC_LOKI_OUTPUT_FILE") {
+        Ok(path) => match std::fs::write(&path, &report) {
+            Ok(_) => println!("\nWrote {} report to {}", format, path),
+            Err(e) => eprintln!("Could not write {} report to {}: {}", format, path, e),
+        },
+        Err(_) => println!("\n{}", report),
+    }
+}
+
+fn main() {
+    println!("{}", "=".repeat(50));
+    println!("OIDC-Loki Rust Client Security Tests");
+    println!("{}", "=".repeat(50));
+
+    let client = Client::new();
+    let allowed_kids = fetch_jwks(&client);
+
+    let results = vec![
+        test_alg_none(&client, &allowed_kids),
+        test_key_confusion(&client, &allowed_kids),
+        test_temporal_tampering(&client, &allowed_kids),
+        test_valid_token(&client),
+        test_code_replay(&client),
+        test_pkce_downgrade(&client),
+        test_redirect_uri_mismatch(&client),
+        test_aud_confusion(&client, &allowed_kids),
+        test_jku_injection(&client, &allowed_kids),
+        test_temporal_nbf(&client, &allowed_kids),
+        test_temporal_iat(&client, &allowed_kids),
+        test_discovery_issuer_mismatch(&client),
+        test_logout_token_forgery(&client),
+        test_logout_token_replay(&client),
+        test_nonce_binding(&client),
+    ];
+
+    // Summary
+    println!("\n{}", "=".repeat(50));
+    println!("Summary");
+    println!("{}", "=".repeat(50));
+
+    let passed = results.iter().filter(|r| r.passed).count();
+    let total = results.len();
+    println!("Passed: {}/{}", passed, total);
+
+    for result in &results {
+        let status = if result.passed { "PASS" } else { "FAIL" };
+        println!("  [{}] {}: {}", status, result.name, result.message);
+    }
+
+    emit_structured_report(&results);
 
     std::process::exit(if passed == total { 0 } else { 1 });
 }